@@ -0,0 +1,193 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use flate2::read::MultiGzDecoder;
+use indicatif::{ProgressBar, ProgressStyle};
+use reqwest::blocking::Client;
+use reqwest::header::{CONTENT_LENGTH, RANGE};
+
+/// `fetch` subcommand: download Common Crawl WARC/WET segments over HTTP
+/// instead of reading files already sitting in `--input-dir`.
+#[derive(clap::Args, Debug)]
+pub struct FetchArgs {
+    /// Common Crawl crawl id, e.g. CC-MAIN-2024-10 (used only for logging)
+    #[arg(long)]
+    pub crawl_id: String,
+
+    /// Path to a file listing segment paths (one per line), as published in
+    /// a crawl's `wet.paths.gz` index
+    #[arg(long)]
+    pub segment_list: String,
+
+    /// Base URL segment paths are resolved against
+    #[arg(long, default_value = "https://data.commoncrawl.org")]
+    pub base_url: String,
+
+    /// Directory to save downloaded segments into
+    #[arg(long, default_value = "output-warc")]
+    pub download_dir: String,
+
+    /// Max attempts per segment on transient errors, with exponential backoff between them
+    #[arg(long, default_value_t = 5)]
+    pub max_retries: usize,
+
+    /// Decompress each downloaded segment in memory and filter it immediately.
+    /// The gzip-compressed segment is saved to `download_dir` with or without
+    /// this flag; no decompressed .warc file is written in either case
+    #[arg(long)]
+    pub pipe: bool,
+}
+
+/// Read a newline-separated list of Common Crawl segment paths, skipping
+/// blank lines and `#`-comments (same convention as the blocklist files).
+pub fn read_segment_list(path: &str) -> std::io::Result<Vec<String>> {
+    let text = fs::read_to_string(path)?;
+    Ok(text
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(str::to_string)
+        .collect())
+}
+
+/// Download one segment to `download_dir`, resuming a partial download via
+/// an HTTP range request and retrying transient errors with exponential
+/// backoff. Returns the local path of the (still gzip-compressed) segment.
+pub fn download_segment(client: &Client, args: &FetchArgs, segment_path: &str) -> Result<PathBuf, String> {
+    let url = format!("{}/{}", args.base_url.trim_end_matches('/'), segment_path);
+    let file_name = Path::new(segment_path)
+        .file_name()
+        .ok_or_else(|| format!("segment path has no file name: {}", segment_path))?
+        .to_string_lossy()
+        .to_string();
+    fs::create_dir_all(&args.download_dir).map_err(|e| e.to_string())?;
+    let dest_path = Path::new(&args.download_dir).join(file_name);
+
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        match try_download(client, &url, &dest_path) {
+            Ok(()) => return Ok(dest_path),
+            Err(e) if e.transient && attempt < args.max_retries => {
+                let backoff = Duration::from_secs(1 << attempt.min(6));
+                eprintln!(
+                    "Download of {} failed ({}), retrying in {:?} (attempt {}/{})",
+                    url, e.message, backoff, attempt, args.max_retries
+                );
+                std::thread::sleep(backoff);
+            }
+            Err(e) if e.transient => {
+                return Err(format!("giving up on {} after {} attempts: {}", url, attempt, e.message))
+            }
+            Err(e) => return Err(format!("{} failed with a permanent error: {}", url, e.message)),
+        }
+    }
+}
+
+/// A failed download attempt, tagged with whether retrying is worth it:
+/// network errors, timeouts, 429, and 5xx are `transient`; everything else
+/// (404, 403, other 4xx) is a permanent error that retrying won't fix.
+struct DownloadError {
+    message: String,
+    transient: bool,
+}
+
+impl From<reqwest::Error> for DownloadError {
+    fn from(e: reqwest::Error) -> Self {
+        DownloadError { message: e.to_string(), transient: true }
+    }
+}
+
+impl From<std::io::Error> for DownloadError {
+    fn from(e: std::io::Error) -> Self {
+        DownloadError { message: e.to_string(), transient: true }
+    }
+}
+
+/// One download attempt: resumes from the existing partial file (if any) via
+/// a `Range: bytes=N-` request and shows progress with `indicatif`.
+fn try_download(client: &Client, url: &str, dest_path: &Path) -> Result<(), DownloadError> {
+    let existing_len = fs::metadata(dest_path).map(|m| m.len()).unwrap_or(0);
+
+    let mut request = client.get(url);
+    if existing_len > 0 {
+        request = request.header(RANGE, format!("bytes={}-", existing_len));
+    }
+
+    let mut response = request.send()?;
+    if response.status().as_u16() == 416 {
+        // Range start was at or past the end of the resource: the partial
+        // file on disk is already the full download, so there's nothing
+        // left to fetch.
+        return Ok(());
+    }
+    if !response.status().is_success() {
+        let status = response.status();
+        let transient = status.as_u16() == 429 || status.is_server_error();
+        return Err(DownloadError { message: format!("unexpected status {}", status), transient });
+    }
+    let resuming = response.status().as_u16() == 206;
+
+    let total_len = response
+        .headers()
+        .get(CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok())
+        .map(|len| if resuming { len + existing_len } else { len });
+
+    let progress = total_len.map(|len| {
+        let bar = ProgressBar::new(len);
+        bar.set_style(
+            ProgressStyle::with_template("{msg} [{bar:40}] {bytes}/{total_bytes} ({eta})")
+                .unwrap()
+                .progress_chars("=> "),
+        );
+        bar.set_position(existing_len);
+        bar.set_message(dest_path.file_name().unwrap().to_string_lossy().to_string());
+        bar
+    });
+
+    let mut out = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(resuming)
+        .truncate(!resuming)
+        .open(dest_path)?;
+
+    let mut buf = [0u8; 64 * 1024];
+    loop {
+        let n = response.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        out.write_all(&buf[..n])?;
+        if let Some(bar) = &progress {
+            bar.inc(n as u64);
+        }
+    }
+    if let Some(bar) = progress {
+        bar.finish_with_message("done");
+    }
+    Ok(())
+}
+
+/// Decompress a downloaded `.warc.gz` segment fully into memory. WARC.gz
+/// files concatenate one gzip member per record, which `MultiGzDecoder`
+/// handles transparently.
+pub fn decompress_segment(gz_path: &Path) -> std::io::Result<Vec<u8>> {
+    let file = File::open(gz_path)?;
+    let mut decoder = MultiGzDecoder::new(file);
+    let mut decompressed = Vec::new();
+    decoder.read_to_end(&mut decompressed)?;
+    Ok(decompressed)
+}
+
+/// Build the blocking HTTP client shared across segment downloads.
+pub fn build_client() -> Client {
+    Client::builder()
+        .timeout(Duration::from_secs(300))
+        .build()
+        .expect("failed to build HTTP client")
+}
@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+use std::fs;
+
+use once_cell::sync::Lazy;
+use roxmltree::{Document, Node};
+
+/// Where the JMdict XML dictionary is expected to live. JMdict itself is a
+/// large, slowly-changing file that isn't worth wiring up as a CLI flag, so
+/// unlike `--blocklist-dir` this path is fixed.
+const JMDICT_PATH: &str = "./JMdict.xml";
+
+/// Longest surface form (in characters) considered as a single token when
+/// greedily matching against the index.
+const MAX_TOKEN_CHARS: usize = 8;
+
+/// Index of every JMdict entry, keyed by each of its kanji (`keb`) and
+/// reading (`reb`) surface forms, built once on first use and shared across
+/// threads -- the same `once_cell::Lazy` pattern `BLOCKED_DOMAINS` used to
+/// follow. Empty (rather than a panic) if `JMDICT_PATH` is missing or fails
+/// to parse; `known_word_ratio` then returns 1.0 for every text, so the
+/// coverage filter passes every record instead of rejecting them all.
+static JMDICT_INDEX: Lazy<HashMap<&'static str, Vec<Node<'static, 'static>>>> =
+    Lazy::new(|| build_index(JMDICT_PATH));
+
+fn build_index(path: &str) -> HashMap<&'static str, Vec<Node<'static, 'static>>> {
+    let mut index = HashMap::new();
+    let xml = match fs::read_to_string(path) {
+        Ok(xml) => xml,
+        Err(_) => return index,
+    };
+    // `roxmltree::Document` borrows from the source string, and `Lazy`
+    // needs a `'static` value, so both the source and the parsed document
+    // are leaked for the life of the process -- JMdict is loaded exactly
+    // once, so this isn't a growing leak.
+    let xml: &'static str = Box::leak(xml.into_boxed_str());
+    let doc = match Document::parse(xml) {
+        Ok(doc) => doc,
+        Err(_) => return index,
+    };
+    let doc: &'static Document<'static> = Box::leak(Box::new(doc));
+
+    for entry in doc.descendants().filter(|n| n.has_tag_name("entry")) {
+        for surface_tag in ["keb", "reb"] {
+            for node in entry.descendants().filter(|n| n.has_tag_name(surface_tag)) {
+                if let Some(text) = node.text() {
+                    index.entry(text).or_insert_with(Vec::new).push(entry);
+                }
+            }
+        }
+    }
+    index
+}
+
+/// Whether `c` falls in the hiragana, katakana, or CJK ideograph blocks
+/// (the same ranges `HIRA_REGEX`/`KATA_REGEX`/`CJK_REGEX` test in `lib.rs`).
+fn is_japanese_char(c: char) -> bool {
+    matches!(c, '\u{3040}'..='\u{309F}' | '\u{30A0}'..='\u{30FF}' | '\u{4E00}'..='\u{9FFF}')
+}
+
+/// Fraction of the tokens in `text`'s Japanese-script runs that are known
+/// JMdict surface forms, via greedy longest-match tokenization: at each Japanese
+/// character, try the longest run (up to `MAX_TOKEN_CHARS`) that is a known
+/// `keb`/`reb` entry, falling back to a single unknown character when
+/// nothing matches. Non-Japanese characters (Latin letters, digits,
+/// punctuation, whitespace) are skipped entirely rather than counted as
+/// unknown tokens, so URLs and numbers in otherwise-fluent Japanese prose
+/// don't deflate the ratio. Returns 1.0 when there's nothing to score
+/// (empty text, or no JMdict index loaded), so callers don't need to
+/// special-case either.
+pub fn known_word_ratio(text: &str) -> f64 {
+    let chars: Vec<char> = text.chars().collect();
+    let index = &*JMDICT_INDEX;
+    if chars.is_empty() || index.is_empty() {
+        return 1.0;
+    }
+
+    let mut total_tokens = 0usize;
+    let mut known_tokens = 0usize;
+    let mut i = 0;
+    while i < chars.len() {
+        if !is_japanese_char(chars[i]) {
+            i += 1;
+            continue;
+        }
+
+        let mut run_len = 0;
+        while i + run_len < chars.len() && run_len < MAX_TOKEN_CHARS && is_japanese_char(chars[i + run_len]) {
+            run_len += 1;
+        }
+
+        let mut matched_len = 0;
+        for len in (1..=run_len).rev() {
+            let candidate: String = chars[i..i + len].iter().collect();
+            if index.contains_key(candidate.as_str()) {
+                matched_len = len;
+                break;
+            }
+        }
+
+        total_tokens += 1;
+        if matched_len > 0 {
+            known_tokens += 1;
+            i += matched_len;
+        } else {
+            i += 1;
+        }
+    }
+
+    if total_tokens == 0 {
+        return 1.0;
+    }
+    known_tokens as f64 / total_tokens as f64
+}
@@ -0,0 +1,819 @@
+use std::fs;
+use std::collections::{HashSet, HashMap};
+use std::path::Path;
+use std::time::{Instant, Duration};
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, Result, Write};
+use url::Url;
+use warc::WarcReader;
+use warc::WarcHeader;
+use rayon::prelude::*;
+use once_cell::sync::Lazy;
+use regex::{Regex, RegexBuilder};
+use html5ever::tendril::TendrilSink;
+use html5ever::parse_document;
+use markup5ever_rcdom::{RcDom, Handle, NodeData};
+use whatlang::{detect, Lang};
+use rand::Rng;
+use clap::Parser;
+use encoding_rs::{Encoding, SHIFT_JIS, EUC_JP, ISO_2022_JP, UTF_8};
+use serde::Serialize;
+
+mod fetch;
+mod jmdict;
+mod warc_types;
+use fetch::FetchArgs;
+use warc_types::CLD2Result;
+
+/// Output format for kept records.
+#[derive(clap::ValueEnum, Clone, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Plain text, records separated by `--- RECORD BOUNDARY ---`
+    Text,
+    /// One JSON object per kept record
+    Jsonl,
+}
+
+impl std::fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            OutputFormat::Text => write!(f, "text"),
+            OutputFormat::Jsonl => write!(f, "jsonl"),
+        }
+    }
+}
+
+/// One kept record, emitted as a single JSON line in `--format jsonl` mode.
+#[derive(Debug, Serialize)]
+struct JsonlRecord {
+    target_uri: String,
+    charset_detected: Option<String>,
+    /// Byte length of the cleaned, kept text -- NOT this record's WARC
+    /// `Content-Length` header (only the text format prints that).
+    cleaned_length: usize,
+    text: String,
+    date_count: usize,
+    lang_detected: Option<String>,
+    dict_coverage: Option<f64>,
+    cld2: Option<CLD2Result>,
+}
+
+/// Top-level CLI: the filtering pipeline's own flags are available directly
+/// (the original, still-default behaviour), and `fetch` adds a subcommand
+/// that downloads Common Crawl segments instead of reading `--input-dir`.
+#[derive(Parser, Debug)]
+#[command(author, version, about = "Filter Common Crawl WET files down to fluent Japanese text")]
+pub struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    pub config: Config,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum Command {
+    /// Download Common Crawl WARC/WET segments over HTTP, optionally filtering each one as soon as it arrives
+    Fetch(FetchArgs),
+}
+
+/// Command-line configuration for the filtering pipeline. Every threshold
+/// the filters read lives here instead of in a compile-time constant, so the same
+/// binary can be retuned per corpus without recompiling.
+#[derive(clap::Args, Debug, Clone)]
+pub struct Config {
+    /// Directory containing input .warc files
+    #[arg(long, default_value = "output-warc")]
+    input_dir: String,
+
+    /// Directory to write output files into
+    #[arg(long, default_value = ".")]
+    output_dir: String,
+
+    /// Directory of UT1 blocklist category subfolders, each with a `domains` file
+    #[arg(long, default_value = "./ut1_blocklist")]
+    blocklist_dir: String,
+
+    /// Cap the rayon thread pool size (0 = rayon's default)
+    #[arg(long, default_value_t = 0)]
+    threads: usize,
+
+    /// Stop after this many records per file (0 = no limit)
+    #[arg(long, default_value_t = 0)]
+    max_records_per_file: usize,
+
+    /// Log progress every N records (0 disables progress logging)
+    #[arg(long, default_value_t = 1000)]
+    progress_interval: usize,
+
+    /// Max characters fed into language detection
+    #[arg(long, default_value_t = 512)]
+    detect_prefix_chars: usize,
+
+    /// n-gram size used by the repeating-ngram filter
+    #[arg(long, default_value_t = 3)]
+    ngram_size: usize,
+
+    /// Reject text whose most frequent n-gram repeats more than this many times
+    #[arg(long, default_value_t = 10)]
+    ngram_repeat_threshold: usize,
+
+    /// Reject pages with more than this many date-list mentions (e.g. `YYYY年M月`)
+    #[arg(long, default_value_t = 5)]
+    month_list_threshold: usize,
+
+    /// Draw a uniform reservoir sample of this many kept records (0 disables sampling)
+    #[arg(long, default_value_t = 0)]
+    sample: usize,
+
+    /// Reject records whose JMdict known-word ratio is below this threshold (0.0 disables the filter)
+    #[arg(long, default_value_t = 0.0)]
+    min_dict_coverage: f64,
+
+    /// Output format for kept records
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    format: OutputFormat,
+}
+
+/// Build the set of blocked domains from each subfolder's `domains` file.
+fn load_blocked_domains(blocklist_dir: &str) -> HashSet<String> {
+    let mut set = HashSet::new();
+    for entry in fs::read_dir(blocklist_dir).unwrap() {
+        let entry = entry.unwrap();
+        if entry.file_type().unwrap().is_dir() {
+            let dom = entry.path().join("domains");
+            if dom.exists() {
+                if let Ok(txt) = fs::read_to_string(&dom) {
+                    for line in txt.lines() {
+                        let l = line.trim();
+                        if !l.is_empty() && !l.starts_with('#') {
+                            set.insert(l.to_string());
+                        }
+                    }
+                }
+            }
+        }
+    }
+    set
+}
+
+const LONG_SENTENCE_LEN: usize = 100; // threshold for 'long' sentences
+
+// Compile regexes once
+static LANG_REGEX: Lazy<Regex> = Lazy::new(|| {
+    RegexBuilder::new(r#"<html\b[^>]*\blang=['"]?([a-zA-Z-]+)['"]?"#)
+        .case_insensitive(true)
+        .build()
+        .unwrap()
+});
+static HIRA_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"[\u3040-\u309F]").unwrap());
+static KATA_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"[\u30A0-\u30FF]").unwrap());
+static CJK_REGEX:  Lazy<Regex> = Lazy::new(|| Regex::new(r"[\u4E00-\u9FFF]").unwrap());
+static DATE_REGEX: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"\d{4}年\d{1,2}月").unwrap()
+});
+static CONTENT_TYPE_CHARSET_REGEX: Lazy<Regex> = Lazy::new(|| {
+    RegexBuilder::new(r"content-type:[^\r\n]*charset=([\w-]+)")
+        .case_insensitive(true)
+        .build()
+        .unwrap()
+});
+static META_CHARSET_REGEX: Lazy<Regex> = Lazy::new(|| {
+    RegexBuilder::new(r#"<meta[^>]+charset=["']?([\w-]+)"#)
+        .case_insensitive(true)
+        .build()
+        .unwrap()
+});
+
+// how many payload bytes to scan for a <meta charset=...> tag
+const META_SNIFF_BYTES: usize = 4096;
+
+/// Work out the charset of an HTTP payload: first the `charset=` parameter
+/// on the HTTP `Content-Type` header, then a `<meta charset=...>` /
+/// `<meta http-equiv="Content-Type" ...>` tag in the payload itself, and
+/// finally a byte-level sniff among the Japanese encodings Common Crawl
+/// pages actually show up in.
+fn detect_charset(http_headers: &str, payload: &[u8]) -> &'static Encoding {
+    if let Some(caps) = CONTENT_TYPE_CHARSET_REGEX.captures(http_headers) {
+        if let Some(enc) = Encoding::for_label(caps[1].as_bytes()) {
+            return enc;
+        }
+    }
+    let prefix_len = payload.len().min(META_SNIFF_BYTES);
+    let prefix = String::from_utf8_lossy(&payload[..prefix_len]);
+    if let Some(caps) = META_CHARSET_REGEX.captures(&prefix) {
+        if let Some(enc) = Encoding::for_label(caps[1].as_bytes()) {
+            return enc;
+        }
+    }
+    sniff_charset(payload)
+}
+
+/// Whether `payload` contains an ISO-2022-JP escape sequence (`ESC $` to
+/// switch into a JIS character set, `ESC (` to switch back to ASCII/JIS
+/// Roman). ISO-2022-JP text always carries these; raw Shift_JIS/EUC-JP
+/// bytes essentially never do, since `ISO_2022_JP.decode` otherwise passes
+/// most such bytes through as ASCII/JIS-Roman without reporting errors,
+/// which would otherwise make it look like the best-decoding candidate.
+fn has_iso2022_escape(payload: &[u8]) -> bool {
+    payload.windows(2).any(|w| w[0] == 0x1B && (w[1] == b'$' || w[1] == b'('))
+}
+
+/// Byte-level charset sniff for payloads with no declared charset: prefer
+/// UTF-8 when it validates, otherwise pick whichever of the common Japanese
+/// legacy encodings decodes with the fewest replacement characters.
+fn sniff_charset(payload: &[u8]) -> &'static Encoding {
+    if std::str::from_utf8(payload).is_ok() {
+        return UTF_8;
+    }
+    let candidates: &[&'static Encoding] = if has_iso2022_escape(payload) {
+        &[SHIFT_JIS, EUC_JP, ISO_2022_JP]
+    } else {
+        &[SHIFT_JIS, EUC_JP]
+    };
+    let mut best = SHIFT_JIS;
+    let mut best_errors = usize::MAX;
+    for &enc in candidates {
+        let (decoded, _, had_errors) = enc.decode(payload);
+        if !had_errors {
+            return enc;
+        }
+        let error_count = decoded.chars().filter(|&c| c == '\u{FFFD}').count();
+        if error_count < best_errors {
+            best_errors = error_count;
+            best = enc;
+        }
+    }
+    best
+}
+
+/// Find the blank-line boundary between HTTP headers and payload in a raw
+/// WARC response body, returning the split index and separator length.
+fn find_header_boundary(body: &[u8]) -> Option<(usize, usize)> {
+    if let Some(i) = body.windows(4).position(|w| w == b"\r\n\r\n") {
+        Some((i, 4))
+    } else if let Some(i) = body.windows(2).position(|w| w == b"\n\n") {
+        Some((i, 2))
+    } else {
+        None
+    }
+}
+
+/// Scan WARC bytes for `metadata` records carrying a Common Crawl CLD2
+/// `languages` JSON blob, keyed by the target URI they describe, so
+/// `--format jsonl` can merge the upstream language verdict into the
+/// matching `response` record's output.
+fn collect_cld2_by_uri(data: &[u8]) -> HashMap<String, CLD2Result> {
+    let mut by_uri = HashMap::new();
+    let reader = WarcReader::new(BufReader::new(std::io::Cursor::new(data)));
+    for record_result in reader.iter_records() {
+        let rec = match record_result {
+            Ok(rec) => rec,
+            Err(_) => continue,
+        };
+        let is_metadata = rec.header(WarcHeader::WarcType).map_or(false, |wt| wt == "metadata");
+        if !is_metadata {
+            continue;
+        }
+        let uri = match rec.header(WarcHeader::TargetURI) {
+            Some(uri) => uri.to_string(),
+            None => continue,
+        };
+        if let Ok(body) = std::str::from_utf8(rec.body()) {
+            if let Some(cld2) = extract_cld2_languages(body) {
+                by_uri.insert(uri, cld2);
+            }
+        }
+    }
+    by_uri
+}
+
+/// Common Crawl `metadata` record bodies are `key: value` lines (e.g.
+/// `fetchTimeMs: 316`), not a single JSON object, so the CLD2 verdict has to
+/// be pulled off its own `languages-cld2: {...}` line before it can be
+/// decoded.
+fn extract_cld2_languages(body: &str) -> Option<CLD2Result> {
+    let value = body
+        .lines()
+        .find_map(|line| line.trim_start().strip_prefix("languages-cld2:"))?;
+    serde_json::from_str(value.trim()).ok()
+}
+
+/// Check <html lang=...> attribute for 'ja'
+fn is_japanese_page_by_lang_regexp(content: &str) -> bool {
+    if let Some(caps) = LANG_REGEX.captures(content) {
+        if let Some(m) = caps.get(1) {
+            // only allow if lang starts with 'ja'
+            return m.as_str().to_lowercase().starts_with("ja");
+        }
+    }
+    // no lang attribute: do not filter out
+    true
+}
+
+/// Fast check for any Japanese text characters
+fn contains_japanese_text(content: &str) -> bool {
+    let has_hira = HIRA_REGEX.is_match(content);
+    let has_kata = KATA_REGEX.is_match(content);
+    let has_cjk  = CJK_REGEX.is_match(content);
+    // require at least two of the three scripts
+    [has_hira, has_kata, has_cjk].iter().filter(|&&b| b).count() >= 2
+}
+
+/// Strip HTML tags using html5ever+RcDom, skipping script/style content
+/// and normalize whitespace in one pass.
+fn strip_tags(input: &str) -> String {
+    let dom: RcDom = parse_document(RcDom::default(), Default::default()).one(input);
+    fn recurse(handle: &Handle, out: &mut String, prev_space: &mut bool) {
+        if let NodeData::Element { name, .. } = &handle.data {
+            let tag = name.local.as_ref();
+            if tag.eq_ignore_ascii_case("script")
+                || tag.eq_ignore_ascii_case("style")
+                || tag.eq_ignore_ascii_case("header")
+                || tag.eq_ignore_ascii_case("footer")
+                || tag.eq_ignore_ascii_case("nav")
+            {
+                return;
+            }
+        }
+        if let NodeData::Text { contents } = &handle.data {
+            for ch in contents.borrow().chars() {
+                if ch.is_whitespace() {
+                    if !*prev_space {
+                        out.push(' ');
+                        *prev_space = true;
+                    }
+                } else {
+                    out.push(ch);
+                    *prev_space = false;
+                }
+            }
+        }
+        for child in handle.children.borrow().iter() {
+            recurse(child, out, prev_space);
+        }
+    }
+    let mut text = String::new();
+    let mut prev_space = true;
+    recurse(&dom.document, &mut text, &mut prev_space);
+    text.trim().to_string()
+}
+
+
+pub fn has_repeating_ngrams(text: &str, n: usize, threshold: usize) -> bool {
+    // 1. 事前処理（小文字化、句読点・記号の除去など）
+    let normalized = text
+        .to_lowercase()
+        .replace(|c: char| !c.is_alphanumeric() && !c.is_whitespace(), "");
+
+    // 2. 単語単位 (word-based) の n-gram
+    let words: Vec<&str> = normalized.split_whitespace().collect();
+    if check_ngram_count(&words, n, threshold) {
+        return true;
+    }
+
+    // 3. 文字単位 (character-based) の n-gram
+    //   全角文字や結合文字を考慮する場合は別途対応が必要
+    //   ここでは単純な例として chars() を使う
+    let chars: Vec<char> = normalized.chars().collect();
+    if check_ngram_count(&chars, n, threshold) {
+        return true;
+    }
+
+    false
+}
+
+fn check_ngram_count<T: Eq + std::hash::Hash + Clone>(
+    tokens: &[T],
+    n: usize,
+    threshold: usize,
+) -> bool {
+    let mut counts = HashMap::new();
+    for window in tokens.windows(n) {
+        // 簡易的にコピーして集合キーを作成
+        let key: Vec<T> = window.to_vec();
+        let cnt = counts.entry(key).or_insert(0);
+        *cnt += 1;
+        if *cnt > threshold {
+            return true;
+        }
+    }
+    false
+}
+
+/// Filter diagnostics recorded for a kept record, so downstream formats
+/// (currently the `--format jsonl` mode) can surface why the n-gram/date
+/// filters let it through alongside the language-detection verdict. There's
+/// no `ngram_repeated` field here: a repeating-n-gram record is rejected
+/// before this struct is ever built, so on the kept path it would always be
+/// `false` and carry no information.
+pub struct TextDiagnostics {
+    pub date_count: usize,
+    pub lang_detected: Option<String>,
+    pub dict_coverage: Option<f64>,
+}
+
+/// Why `process_text` rejected a candidate record, so callers (and the
+/// accuracy test suite) can assert *why* a record was dropped instead of
+/// just that it was.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RejectReason {
+    /// `<html lang=...>` declared a non-Japanese language
+    NonJapaneseLang,
+    /// Fewer than two of hiragana/katakana/CJK scripts present
+    NotJapaneseText,
+    /// More `YYYY年M月`-style mentions than `--month-list-threshold` allows
+    TooManyDateMentions,
+    /// Most frequent n-gram repeats past `--ngram-repeat-threshold`
+    RepeatingNgrams,
+    /// `whatlang` did not detect Japanese on the cleaned text's prefix
+    NotJapaneseDetected,
+    /// JMdict known-word ratio fell below `--min-dict-coverage`
+    LowDictCoverage,
+}
+
+/// Process and filter text. Returns `Ok((cleaned_text, diagnostics))` if the
+/// record should be kept, `Err(reason)` otherwise.
+pub fn process_text(
+    text: &str,
+    config: &Config,
+    detect_time: &mut Duration,
+    tag_time: &mut Duration,
+    _filter_time: &mut Duration,
+) -> std::result::Result<(String, TextDiagnostics), RejectReason> {
+    // prefilter by HTML lang & Japanese scripts
+    if !is_japanese_page_by_lang_regexp(text) {
+        return Err(RejectReason::NonJapaneseLang);
+    }
+    if !contains_japanese_text(text) {
+        return Err(RejectReason::NotJapaneseText);
+    }
+
+    // タグ除去
+    let extracted = strip_tags(text);
+
+    // 年月リストが多すぎるパターンを除外
+    let date_count = DATE_REGEX.find_iter(&extracted).count();
+    if date_count > config.month_list_threshold {
+        return Err(RejectReason::TooManyDateMentions);
+    }
+
+    // n-gram 重複チェック
+    if has_repeating_ngrams(&extracted, config.ngram_size, config.ngram_repeat_threshold) {
+        println!("Skipping due to repeating n-grams");
+        return Err(RejectReason::RepeatingNgrams);
+    }
+
+    // ※ split_whitespace().join(" ") は strip_tags 内で済んでいるので削除
+    let extracted = extracted;
+
+    // 言語検出 (既存)
+    let prefix: String = extracted.chars().take(config.detect_prefix_chars).collect();
+    let dt_start = Instant::now();
+    let lang_info = detect(&prefix);
+    *detect_time += dt_start.elapsed();
+    let is_jpn = matches!(lang_info, Some(info) if info.lang() == Lang::Jpn);
+    if !is_jpn {
+        return Err(RejectReason::NotJapaneseDetected);
+    }
+    let lang_detected = lang_info.map(|info| info.lang().code().to_string());
+
+    // JMdict 既知語カバレッジチェック
+    let dict_coverage = if config.min_dict_coverage > 0.0 {
+        let ratio = jmdict::known_word_ratio(&extracted);
+        if ratio < config.min_dict_coverage {
+            return Err(RejectReason::LowDictCoverage);
+        }
+        Some(ratio)
+    } else {
+        None
+    };
+
+    Ok((extracted, TextDiagnostics { date_count, lang_detected, dict_coverage }))
+}
+
+/// Process a single WET file read from disk. Thin wrapper around
+/// `process_wet_data` that reads the whole file into memory up front so the
+/// same core loop can also drive the `fetch --pipe` path, which decompresses
+/// a downloaded segment in memory; the gzip-compressed segment itself is
+/// still saved to `--download-dir` first, but no decompressed .warc is written.
+pub fn process_wet_file(path: &str, config: &Config, blocked_domains: &HashSet<String>) -> Result<(Vec<String>, usize)> {
+    let data = fs::read(path)?;
+    let file_stem = Path::new(path).file_stem().unwrap().to_string_lossy().to_string();
+    let output_filename = match config.format {
+        OutputFormat::Text => Path::new(&config.output_dir).join(format!("japanese_html_{}.txt", file_stem)),
+        OutputFormat::Jsonl => Path::new(&config.output_dir).join(format!("japanese_records_{}.jsonl", file_stem)),
+    };
+    process_wet_data(&data, path, &output_filename, config, blocked_domains)
+}
+
+/// Process WET records already held in memory, writing kept records to
+/// `output_filename`. When `config.sample` is 0 every kept record is written
+/// straight through (the original behaviour); otherwise the kept records are
+/// drawn down to a uniform `config.sample`-record reservoir via Algorithm R
+/// before being flushed. The returned reservoir and kept-record count let
+/// the caller fold per-source samples into one global sample. `label` is
+/// used only for progress/error logging, so it can be a file path or a
+/// fetched segment's URL.
+pub fn process_wet_data(
+    data: &[u8],
+    label: &str,
+    output_filename: &Path,
+    config: &Config,
+    blocked_domains: &HashSet<String>,
+) -> Result<(Vec<String>, usize)> {
+    println!("--- Processing {} ---", label);
+
+    let sample_size = config.sample;
+
+    let mut html_output = OpenOptions::new().create(true).write(true).truncate(true).open(output_filename)?;
+    println!("Saving Japanese content to {}", output_filename.display());
+    let mut reservoir: Vec<String> = if sample_size > 0 { Vec::with_capacity(sample_size) } else { Vec::new() };
+    let mut rng = rand::thread_rng();
+
+    // Common Crawl metadata records carrying a CLD2 language verdict for
+    // each URI, only needed in JSONL mode where we merge it into output.
+    let cld2_by_uri = if config.format == OutputFormat::Jsonl {
+        collect_cld2_by_uri(data)
+    } else {
+        HashMap::new()
+    };
+
+    println!("Reading records (progress every {} records)...", config.progress_interval);
+    std::io::stdout().flush().unwrap();
+    let file_start = Instant::now();
+    // Performance timers
+    let mut total_detect_time = Duration::ZERO;
+    let mut total_tag_time    = Duration::ZERO;
+    let mut total_filter_time = Duration::ZERO;
+    let mut record_count = 0;
+    let mut kept_count = 0; // count of records that passed filters
+
+    let reader = WarcReader::new(BufReader::new(std::io::Cursor::new(data)));
+    for record_result in reader.iter_records() {
+        // Optional record limit
+        if config.max_records_per_file > 0 && record_count >= config.max_records_per_file {
+            println!("Record limit {} reached, stopping.", config.max_records_per_file);
+            break;
+        }
+        record_count += 1;
+        // Progress monitoring
+        if config.progress_interval > 0 && record_count % config.progress_interval == 0 {
+            let elapsed = file_start.elapsed();
+            println!("… {} records processed ({:.2?}) …", record_count, elapsed);
+            std::io::stdout().flush().unwrap();
+        }
+        if let Ok(rec) = record_result {
+            // host-based skip
+            let uri = rec.header(WarcHeader::TargetURI).unwrap_or_default();
+            if let Ok(parsed) = Url::parse(uri.as_ref()) {
+                if let Some(host) = parsed.host_str() {
+                    if blocked_domains.contains(host) {
+                        continue;
+                    }
+                }
+            }
+
+            // extract raw HTTP headers and body; the payload may not be
+            // UTF-8 at all (Shift_JIS/EUC-JP pages are common), so split on
+            // the header/body boundary at the byte level first.
+            let body_bytes = rec.body();
+            let (hdr_bytes, payload_bytes) = match find_header_boundary(body_bytes) {
+                Some((i, sep_len)) => (&body_bytes[..i], &body_bytes[i + sep_len..]),
+                None => continue,
+            };
+            let http_headers = String::from_utf8_lossy(hdr_bytes);
+
+            let is_response = rec.header(WarcHeader::WarcType)
+                .map_or(false, |wt| wt == "response");
+            let has_application_http = rec.header(WarcHeader::ContentType)
+                .map_or(false, |ct| ct.contains("application/http"));
+            if !is_response || !has_application_http {
+                continue;
+            }
+
+            // decode the payload with its detected charset before filtering
+            let encoding = detect_charset(&http_headers, payload_bytes);
+            let (decoded, _, _) = encoding.decode(payload_bytes);
+            let text = decoded.into_owned();
+
+             // Process and write cleaned text
+             if let Ok((cleaned, diagnostics)) = process_text(
+                &text,
+                config,
+                &mut total_detect_time,
+                &mut total_tag_time,
+                &mut total_filter_time,
+            ) {
+                // build the output record (the text format carries the WARC
+                // headers, including Content-Length; JSONL carries cleaned_length)
+                let wt  = rec.header(WarcHeader::WarcType).unwrap_or_default();
+                let uri = rec.header(WarcHeader::TargetURI).unwrap_or_default();
+                let cl  = rec.header(WarcHeader::ContentLength).unwrap_or_default();
+                let ct  = rec.header(WarcHeader::ContentType).unwrap_or_default();
+                let cld2 = cld2_by_uri.get(uri.as_ref()).cloned();
+                let charset_detected = encoding.name();
+
+                let record_str = match config.format {
+                    OutputFormat::Text => format!(
+                        "WARC-Type: {}\nWARC-Target-URI: {}\nWARC-Content-Length: {}\nWARC-Content-Type: {}\nWARC-Detected-Charset: {}\n\n{}\n\n--- RECORD BOUNDARY ---\n\n",
+                        wt.as_ref(), uri.as_ref(), cl.as_ref(), ct.as_ref(),
+                        charset_detected,
+                        cleaned
+                    ),
+                    OutputFormat::Jsonl => {
+                        let json_record = JsonlRecord {
+                            target_uri: uri.to_string(),
+                            charset_detected: Some(charset_detected.to_string()),
+                            cleaned_length: cleaned.len(),
+                            text: cleaned,
+                            date_count: diagnostics.date_count,
+                            lang_detected: diagnostics.lang_detected,
+                            dict_coverage: diagnostics.dict_coverage,
+                            cld2,
+                        };
+                        format!("{}\n", serde_json::to_string(&json_record).unwrap())
+                    }
+                };
+
+                if sample_size == 0 {
+                    html_output.write_all(record_str.as_bytes()).unwrap();
+                } else if kept_count < sample_size {
+                    reservoir.push(record_str);
+                } else {
+                    let j = rng.gen_range(0..=kept_count);
+                    if j < sample_size {
+                        reservoir[j] = record_str;
+                    }
+                }
+                kept_count += 1;
+            }
+        } else if let Err(e) = record_result {
+            eprintln!("Error reading record in {}: {}", label, e);
+        }
+    }
+    let elapsed = file_start.elapsed();
+    println!("Kept {} records for {} (out of {})", kept_count, label, record_count);
+    println!("Processed {} records in {:.2?}", record_count, elapsed);
+    // Report detailed performance
+    println!("Total detect time: {:.2?} ({:.1}% of total)", total_detect_time,
+             total_detect_time.as_secs_f64()/elapsed.as_secs_f64()*100.0);
+    println!(
+        "Total TAG removal time: {:.2?} ({:.1}% of total)",
+        total_tag_time,
+        total_tag_time.as_secs_f64() / elapsed.as_secs_f64() * 100.0
+    );
+    println!(
+        "Total sentence-filter time: {:.2?} ({:.1}% of total)",
+        total_filter_time,
+        total_filter_time.as_secs_f64() / elapsed.as_secs_f64() * 100.0
+    );
+    // process_text 全体の合計時間と割合
+    let total_process_text = total_detect_time + total_tag_time + total_filter_time;
+    println!(
+        "Total process_text time: {:.2?} ({:.1}% of total)",
+        total_process_text,
+        total_process_text.as_secs_f64() / elapsed.as_secs_f64() * 100.0
+    );
+
+    if sample_size > 0 {
+        println!("Flushing reservoir of {} sampled records for {}", reservoir.len(), label);
+        for record_str in &reservoir {
+            html_output.write_all(record_str.as_bytes()).unwrap();
+        }
+    }
+
+    Ok((reservoir, kept_count))
+}
+
+/// Merge per-file reservoirs into one global reservoir of `sample_size`
+/// records using weighted reservoir sampling (Chao's algorithm): each
+/// record's weight is its file's kept-record count divided by that file's
+/// reservoir length, so files contribute to the final sample in proportion
+/// to how many records they actually accepted, keeping the global sample
+/// uniform even though per-file reservoirs were drawn from streams of very
+/// different lengths.
+fn merge_reservoirs(per_file: Vec<(Vec<String>, usize)>, sample_size: usize) -> Vec<String> {
+    let mut rng = rand::thread_rng();
+    let mut reservoir: Vec<String> = Vec::with_capacity(sample_size);
+    let mut weight_sum = 0.0f64;
+    for (records, kept_count) in per_file {
+        if records.is_empty() {
+            continue;
+        }
+        let weight = kept_count as f64 / records.len() as f64;
+        for record_str in records {
+            weight_sum += weight;
+            if reservoir.len() < sample_size {
+                reservoir.push(record_str);
+            } else {
+                let p = (sample_size as f64 * weight / weight_sum).min(1.0);
+                if rng.gen::<f64>() < p {
+                    let idx = rng.gen_range(0..sample_size);
+                    reservoir[idx] = record_str;
+                }
+            }
+        }
+    }
+    reservoir
+}
+
+/// Entry point called by `main()` with the parsed `Cli`: runs `fetch` when
+/// that subcommand is given, the local-file filtering pipeline otherwise.
+pub fn run(cli: Cli) -> Result<()> {
+    match cli.command {
+        Some(Command::Fetch(fetch_args)) => run_fetch(&fetch_args, &cli.config),
+        None => run_filter(&cli.config),
+    }
+}
+
+/// Download every segment in `fetch_args.segment_list`. Without `--pipe`
+/// each segment is just saved (resumably) to `--download-dir`; with
+/// `--pipe` each one is decompressed in memory and handed straight to
+/// `process_wet_data`, so the decompressed .warc text never touches disk.
+fn run_fetch(fetch_args: &FetchArgs, config: &Config) -> Result<()> {
+    let segment_paths = fetch::read_segment_list(&fetch_args.segment_list)?;
+    println!(
+        "Fetching crawl {} ({} segments from {})",
+        fetch_args.crawl_id, segment_paths.len(), fetch_args.segment_list
+    );
+    let client = fetch::build_client();
+    let blocked_domains = if fetch_args.pipe {
+        load_blocked_domains(&config.blocklist_dir)
+    } else {
+        HashSet::new()
+    };
+
+    for segment_path in &segment_paths {
+        let gz_path = match fetch::download_segment(&client, fetch_args, segment_path) {
+            Ok(path) => path,
+            Err(e) => {
+                eprintln!("Skipping {}: {}", segment_path, e);
+                continue;
+            }
+        };
+
+        if fetch_args.pipe {
+            let data = fetch::decompress_segment(&gz_path)?;
+            let stem = Path::new(segment_path).file_stem().unwrap_or_default().to_string_lossy().to_string();
+            let output_filename = match config.format {
+                OutputFormat::Text => Path::new(&config.output_dir).join(format!("japanese_html_{}.txt", stem)),
+                OutputFormat::Jsonl => Path::new(&config.output_dir).join(format!("japanese_records_{}.jsonl", stem)),
+            };
+            if let Err(e) = process_wet_data(&data, segment_path, &output_filename, config, &blocked_domains) {
+                eprintln!("Error processing {}: {}", segment_path, e);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn run_filter(config: &Config) -> Result<()> {
+    if config.threads > 0 {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(config.threads)
+            .build_global()
+            .expect("failed to configure rayon thread pool");
+    }
+
+    let blocked_domains = load_blocked_domains(&config.blocklist_dir);
+    println!("Loaded blocked domains: {}", blocked_domains.len());
+
+    if config.sample > 0 {
+        println!("Reservoir sampling enabled: {} records per global sample", config.sample);
+    }
+
+    // Collect all .warc file paths
+    let paths: Vec<String> = fs::read_dir(&config.input_dir)?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.extension().and_then(|s| s.to_str()) == Some("warc"))
+        .filter_map(|p| p.to_str().map(|s| s.to_string()))
+        .collect();
+
+    // Process files in parallel, collecting each file's reservoir (empty
+    // unless sampling is enabled) so a global sample can be merged after.
+    let per_file: Vec<(Vec<String>, usize)> = paths
+        .par_iter()
+        .filter_map(|path| match process_wet_file(path, config, &blocked_domains) {
+            Ok(result) => Some(result),
+            Err(e) => {
+                eprintln!("Error processing {}: {}", path, e);
+                None
+            }
+        })
+        .collect();
+
+    if config.sample > 0 {
+        let merged = merge_reservoirs(per_file, config.sample);
+        let global_filename = Path::new(&config.output_dir).join("japanese_sample_global.txt");
+        let mut global_output = OpenOptions::new().create(true).write(true).truncate(true).open(&global_filename)?;
+        println!("Writing {} globally sampled records to {}", merged.len(), global_filename.display());
+        for record_str in &merged {
+            global_output.write_all(record_str.as_bytes())?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,109 @@
+use std::collections::HashSet;
+use std::time::Duration;
+
+use clap::Parser;
+use cc_parser::{process_text, process_wet_data, Cli, RejectReason};
+
+/// One labelled fixture: a tiny on-disk WARC file and whether its single
+/// response record should survive the filtering pipeline.
+struct Fixture {
+    name: &'static str,
+    file: &'static str,
+    expect_keep: bool,
+}
+
+const CORPUS: &[Fixture] = &[
+    Fixture { name: "keep_japanese", file: "tests/data/keep_japanese.warc", expect_keep: true },
+    Fixture { name: "keep_shiftjis", file: "tests/data/keep_shiftjis.warc", expect_keep: true },
+    Fixture { name: "drop_english", file: "tests/data/drop_english.warc", expect_keep: false },
+    Fixture { name: "drop_monthlist", file: "tests/data/drop_monthlist.warc", expect_keep: false },
+    Fixture { name: "drop_repeating_ngram", file: "tests/data/drop_repeating_ngram.warc", expect_keep: false },
+];
+
+/// Runs the golden corpus through `process_wet_data` with default settings
+/// and checks precision/recall against the "keep" label stay above 0.8,
+/// so tuning a threshold can't silently tank recall on real pages.
+#[test]
+fn golden_corpus_precision_recall() {
+    let config = Cli::parse_from(["cc_parser"]).config;
+    let blocked_domains = HashSet::new();
+
+    let mut true_positive = 0;
+    let mut false_positive = 0;
+    let mut false_negative = 0;
+
+    for fixture in CORPUS {
+        let data = std::fs::read(fixture.file).unwrap_or_else(|e| panic!("reading {}: {}", fixture.file, e));
+        let output_path = std::env::temp_dir().join(format!("cc_parser_accuracy_{}.out", fixture.name));
+        let (_, kept_count) = process_wet_data(&data, fixture.file, &output_path, &config, &blocked_domains)
+            .unwrap_or_else(|e| panic!("processing {}: {}", fixture.file, e));
+        let _ = std::fs::remove_file(&output_path);
+
+        let kept = kept_count > 0;
+        match (fixture.expect_keep, kept) {
+            (true, true) => true_positive += 1,
+            (true, false) => false_negative += 1,
+            (false, true) => false_positive += 1,
+            (false, false) => {}
+        }
+    }
+
+    let precision = true_positive as f64 / (true_positive + false_positive).max(1) as f64;
+    let recall = true_positive as f64 / (true_positive + false_negative).max(1) as f64;
+    assert!(precision >= 0.8, "precision {} below threshold", precision);
+    assert!(recall >= 0.8, "recall {} below threshold", recall);
+}
+
+/// Spot-checks that specific bad inputs are rejected for the *expected*
+/// reason, now that `process_text` reports a structured `RejectReason`
+/// instead of a bare `None`.
+#[test]
+fn reject_reasons_are_specific() {
+    let config = Cli::parse_from(["cc_parser"]).config;
+    let mut detect_time = Duration::ZERO;
+    let mut tag_time = Duration::ZERO;
+    let mut filter_time = Duration::ZERO;
+
+    let english = r#"<html lang="en"><body><p>A short English paragraph about the weather.</p></body></html>"#;
+    assert!(matches!(
+        process_text(english, &config, &mut detect_time, &mut tag_time, &mut filter_time),
+        Err(RejectReason::NonJapaneseLang)
+    ));
+
+    let too_many_dates =
+        "<html lang=\"ja\"><body><p>2020年1月 2020年2月 2020年3月 2020年4月 2020年5月 2020年6月 こんにちは世界</p></body></html>";
+    assert!(matches!(
+        process_text(too_many_dates, &config, &mut detect_time, &mut tag_time, &mut filter_time),
+        Err(RejectReason::TooManyDateMentions)
+    ));
+
+    let repeating = format!(
+        "<html lang=\"ja\"><body><p>{}</p></body></html>",
+        "とても良い商品でした。 ".repeat(20)
+    );
+    assert!(matches!(
+        process_text(&repeating, &config, &mut detect_time, &mut tag_time, &mut filter_time),
+        Err(RejectReason::RepeatingNgrams)
+    ));
+}
+
+/// In JSONL mode, a `metadata` record's CLD2 verdict should be merged into
+/// the matching `response` record's output by shared target URI.
+#[test]
+fn jsonl_output_merges_cld2_from_metadata_record() {
+    let config = Cli::parse_from(["cc_parser", "--format", "jsonl"]).config;
+    let blocked_domains = HashSet::new();
+
+    let file = "tests/data/keep_with_cld2.warc";
+    let data = std::fs::read(file).unwrap_or_else(|e| panic!("reading {}: {}", file, e));
+    let output_path = std::env::temp_dir().join("cc_parser_accuracy_keep_with_cld2.out");
+    let (_, kept_count) = process_wet_data(&data, file, &output_path, &config, &blocked_domains)
+        .unwrap_or_else(|e| panic!("processing {}: {}", file, e));
+    assert_eq!(kept_count, 1, "expected the response record to be kept");
+
+    let output = std::fs::read_to_string(&output_path).unwrap();
+    let _ = std::fs::remove_file(&output_path);
+    let record: serde_json::Value = serde_json::from_str(output.lines().next().unwrap()).unwrap();
+    assert_eq!(record["cld2"]["reliable"], true);
+    assert_eq!(record["cld2"]["languages"][0]["code"], "ja");
+}
@@ -1,8 +1,8 @@
 use std::collections::HashMap;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 /// WARC レコードの汎用構造
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct WarcRecord {
     pub version: String,
     pub headers: HashMap<String, String>,
@@ -10,7 +10,7 @@ pub struct WarcRecord {
 }
 
 /// メタデータレコード
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct MetadataRecord {
     pub record: WarcRecord,
     pub fetch_time_ms: Option<u64>,
@@ -19,14 +19,14 @@ pub struct MetadataRecord {
 }
 
 /// HTML レスポンスレコード
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct HtmlRecord {
     pub record: WarcRecord,
     pub html: String,
 }
 
 /// CLD2 言語検出結果
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CLD2Result {
     pub reliable: bool,
     #[serde(rename = "text-bytes")]
@@ -35,7 +35,7 @@ pub struct CLD2Result {
 }
 
 /// 各言語情報
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LangInfo {
     pub code: String,
     #[serde(rename = "code-iso-639-3")]